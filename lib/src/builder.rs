@@ -1,4 +1,9 @@
-use crate::{grpc_server::RuleItem, GrpcServer};
+use std::{path::Path, sync::Arc, time::SystemTime};
+
+use crate::{
+    fixture, grpc_server::RuleItem, matcher::CustomMatcher, GrpcServer, Matcher, Pagination,
+    StreamFault,
+};
 
 pub trait Then {
     fn return_status(self, status: tonic::Code) -> Self;
@@ -7,6 +12,63 @@ pub trait Then {
     where
         F: Fn() -> T,
         T: prost::Message;
+
+    /// Respond with a stream of messages, for server streaming RPCs.
+    fn return_stream<T, F>(self, f: F) -> Self
+    where
+        F: Fn() -> Vec<T>,
+        T: prost::Message;
+
+    /// Respond with a stream of messages read from a newline-delimited JSON file,
+    /// `f` maps each line to a message.
+    ///
+    /// ## Panics
+    /// * When the file can not be read or a line is not valid JSON
+    fn return_stream_from_ndjson<T, F, P>(self, path: P, f: F) -> Self
+    where
+        P: AsRef<Path>,
+        F: Fn(serde_json::Value) -> T,
+        T: prost::Message;
+
+    /// Respond with a stream of messages read from a binary file, where each message
+    /// is prefixed by its length as a varint (see [`prost::Message::encode_length_delimited`]).
+    ///
+    /// ## Panics
+    /// * When the file can not be read or is truncated
+    fn return_stream_from_length_delimited<P>(self, path: P) -> Self
+    where
+        P: AsRef<Path>;
+
+    /// Apply an ordering anomaly to the streamed messages.
+    fn with_stream_fault(self, fault: StreamFault) -> Self;
+
+    /// Respond with a message created for each request, `f` is given the current time
+    /// of the server (see [`GrpcServer::set_clock_skew`]).
+    fn return_body_with<T, F>(self, f: F) -> Self
+    where
+        F: Fn(SystemTime) -> T + Send + Sync + 'static,
+        T: prost::Message;
+
+    /// Respond with the page of items requested, see [`Pagination`].
+    ///
+    /// ## Panics
+    /// * When the page token or the response of the pagination is not set
+    fn return_pages<T>(self, pagination: Pagination<T>) -> Self
+    where
+        T: Clone + Send + Sync + 'static;
+}
+
+type RespondFn =
+    dyn Fn(&tonic::Request<Vec<u8>>, SystemTime) -> Result<Vec<u8>, tonic::Status> + Send + Sync;
+
+/// Creates the encoded response message for a request, given the current time of the server.
+#[derive(Clone)]
+pub(crate) struct Responder(pub(crate) Arc<RespondFn>);
+
+impl std::fmt::Debug for Responder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Responder")
+    }
 }
 
 pub trait Mountable {
@@ -17,18 +79,42 @@ pub trait Mountable {
 #[derive(Debug, Clone)]
 pub struct MockBuilder {
     pub(crate) path: String,
+    pub(crate) matchers: Vec<Arc<dyn Matcher>>,
     pub(crate) status_code: Option<tonic::Code>,
     pub(crate) result: Option<Vec<u8>>,
+    pub(crate) stream: Option<Vec<Vec<u8>>>,
+    pub(crate) stream_fault: Option<StreamFault>,
+    pub(crate) responder: Option<Responder>,
+    pub(crate) tags: Vec<String>,
+    /// Set when the rule was imported from a stub mapping, the resolved matcher is part of `matchers`.
+    pub(crate) custom_matcher: Option<CustomMatcher>,
 }
 
 #[derive(Clone)]
 pub struct WhenBuilder {
     path: Option<String>,
+    matchers: Vec<Arc<dyn Matcher>>,
 }
 impl WhenBuilder {
     pub fn path(&self, p: &str) -> Self {
         Self {
             path: Some(p.into()),
+            ..self.clone()
+        }
+    }
+
+    /// Only match requests for which the given [`Matcher`] returns `true`.
+    /// Can be called multiple times, all matchers must match.
+    pub fn matching<M>(&self, m: M) -> Self
+    where
+        M: Matcher + 'static,
+    {
+        let mut matchers = self.matchers.clone();
+        matchers.push(Arc::new(m));
+
+        Self {
+            matchers,
+            ..self.clone()
         }
     }
 
@@ -36,8 +122,13 @@ impl WhenBuilder {
         self.validate();
         ThenBuilder {
             path: self.path.clone().unwrap(),
+            matchers: self.matchers.clone(),
             status_code: None,
             result: None,
+            stream: None,
+            stream_fault: None,
+            responder: None,
+            tags: Vec::default(),
         }
     }
 
@@ -51,34 +142,84 @@ impl WhenBuilder {
 #[derive(Clone)]
 pub struct ThenBuilder {
     pub(crate) path: String,
+    pub(crate) matchers: Vec<Arc<dyn Matcher>>,
     pub(crate) status_code: Option<tonic::Code>,
     pub(crate) result: Option<Vec<u8>>,
+    pub(crate) stream: Option<Vec<Vec<u8>>>,
+    pub(crate) stream_fault: Option<StreamFault>,
+    pub(crate) responder: Option<Responder>,
+    pub(crate) tags: Vec<String>,
 }
 
 impl MockBuilder {
     pub fn given(path: &str) -> Self {
         Self {
             path: path.into(),
+            matchers: Vec::default(),
             result: None,
             status_code: None,
+            stream: None,
+            stream_fault: None,
+            responder: None,
+            tags: Vec::default(),
+            custom_matcher: None,
         }
     }
 
     pub fn when() -> WhenBuilder {
-        WhenBuilder { path: None }
+        WhenBuilder {
+            path: None,
+            matchers: Vec::default(),
+        }
+    }
+
+    /// Only match requests for which the given [`Matcher`] returns `true`.
+    /// Can be called multiple times, all matchers must match.
+    pub fn matching<M>(self, m: M) -> Self
+    where
+        M: Matcher + 'static,
+    {
+        let mut matchers = self.matchers;
+        matchers.push(Arc::new(m));
+
+        Self { matchers, ..self }
+    }
+
+    /// Attach a tag to the rule, to later find requests using [`GrpcServer::calls_with_tag`].
+    /// Can be called multiple times.
+    pub fn with_tag(self, tag: &str) -> Self {
+        let mut tags = self.tags;
+        tags.push(tag.into());
+
+        Self { tags, ..self }
+    }
+}
+
+impl ThenBuilder {
+    /// Attach a tag to the rule, to later find requests using [`GrpcServer::calls_with_tag`].
+    /// Can be called multiple times.
+    pub fn with_tag(self, tag: &str) -> Self {
+        let mut tags = self.tags;
+        tags.push(tag.into());
+
+        Self { tags, ..self }
     }
 }
 
 impl Mountable for MockBuilder {
     fn mount(self, s: &mut GrpcServer) {
-        if self.status_code.is_none() && self.result.is_none() {
+        if self.status_code.is_none()
+            && self.result.is_none()
+            && self.stream.is_none()
+            && self.responder.is_none()
+        {
             panic!("Must set the status code or body before attempting to mount the rule.");
         }
 
         s.rules.write().unwrap().push(RuleItem {
             invocations_count: 0,
             invocations: Vec::default(),
-            rule: self,
+            rule: Arc::new(self),
         })
     }
 }
@@ -98,7 +239,7 @@ impl Then for MockBuilder {
     {
         let result = f();
         let mut buf = prost::bytes::BytesMut::new();
-        let _ = result
+        result
             .encode(&mut buf)
             .expect("Unable to encode the message");
         let result = buf.to_vec();
@@ -108,6 +249,72 @@ impl Then for MockBuilder {
             ..self
         }
     }
+
+    fn return_stream<T, F>(self, f: F) -> Self
+    where
+        F: Fn() -> Vec<T>,
+        T: prost::Message,
+    {
+        let stream = f().iter().map(encode).collect();
+
+        Self {
+            stream: Some(stream),
+            ..self
+        }
+    }
+
+    fn return_stream_from_ndjson<T, F, P>(self, path: P, f: F) -> Self
+    where
+        P: AsRef<Path>,
+        F: Fn(serde_json::Value) -> T,
+        T: prost::Message,
+    {
+        Self {
+            stream: Some(fixture::read_ndjson(path.as_ref(), f)),
+            ..self
+        }
+    }
+
+    fn return_stream_from_length_delimited<P>(self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            stream: Some(fixture::read_length_delimited(path.as_ref())),
+            ..self
+        }
+    }
+
+    fn with_stream_fault(self, fault: StreamFault) -> Self {
+        Self {
+            stream_fault: Some(fault),
+            ..self
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn return_body_with<T, F>(self, f: F) -> Self
+    where
+        F: Fn(SystemTime) -> T + Send + Sync + 'static,
+        T: prost::Message,
+    {
+        Self {
+            responder: Some(Responder(Arc::new(
+                move |_, now| Ok(f(now).encode_to_vec()),
+            ))),
+            ..self
+        }
+    }
+
+    fn return_pages<T>(self, pagination: Pagination<T>) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        Self {
+            responder: Some(pagination.into_responder()),
+            ..self
+        }
+    }
 }
 
 impl Then for ThenBuilder {
@@ -125,7 +332,7 @@ impl Then for ThenBuilder {
     {
         let result = f();
         let mut buf = prost::bytes::BytesMut::new();
-        let _ = result
+        result
             .encode(&mut buf)
             .expect("Unable to encode the message");
         let result = buf.to_vec();
@@ -135,6 +342,80 @@ impl Then for ThenBuilder {
             ..self
         }
     }
+
+    fn return_stream<T, F>(self, f: F) -> Self
+    where
+        F: Fn() -> Vec<T>,
+        T: prost::Message,
+    {
+        let stream = f().iter().map(encode).collect();
+
+        Self {
+            stream: Some(stream),
+            ..self
+        }
+    }
+
+    fn return_stream_from_ndjson<T, F, P>(self, path: P, f: F) -> Self
+    where
+        P: AsRef<Path>,
+        F: Fn(serde_json::Value) -> T,
+        T: prost::Message,
+    {
+        Self {
+            stream: Some(fixture::read_ndjson(path.as_ref(), f)),
+            ..self
+        }
+    }
+
+    fn return_stream_from_length_delimited<P>(self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            stream: Some(fixture::read_length_delimited(path.as_ref())),
+            ..self
+        }
+    }
+
+    fn with_stream_fault(self, fault: StreamFault) -> Self {
+        Self {
+            stream_fault: Some(fault),
+            ..self
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn return_body_with<T, F>(self, f: F) -> Self
+    where
+        F: Fn(SystemTime) -> T + Send + Sync + 'static,
+        T: prost::Message,
+    {
+        Self {
+            responder: Some(Responder(Arc::new(
+                move |_, now| Ok(f(now).encode_to_vec()),
+            ))),
+            ..self
+        }
+    }
+
+    fn return_pages<T>(self, pagination: Pagination<T>) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        Self {
+            responder: Some(pagination.into_responder()),
+            ..self
+        }
+    }
+}
+
+fn encode<T: prost::Message>(message: &T) -> Vec<u8> {
+    let mut buf = prost::bytes::BytesMut::new();
+    message
+        .encode(&mut buf)
+        .expect("Unable to encode the message");
+    buf.to_vec()
 }
 
 #[allow(clippy::from_over_into)]
@@ -142,8 +423,14 @@ impl Into<MockBuilder> for ThenBuilder {
     fn into(self) -> MockBuilder {
         MockBuilder {
             path: self.path,
+            matchers: self.matchers,
             status_code: self.status_code,
             result: self.result,
+            stream: self.stream,
+            stream_fault: self.stream_fault,
+            responder: self.responder,
+            tags: self.tags,
+            custom_matcher: None,
         }
     }
 }
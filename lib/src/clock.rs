@@ -0,0 +1,54 @@
+use std::{
+    sync::RwLock,
+    time::{Duration, SystemTime},
+};
+
+use crate::GrpcServer;
+
+/// Offset of the mock server's clock from the system clock, see [`GrpcServer::set_clock_skew`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClockSkew {
+    /// Same as the system clock.
+    #[default]
+    None,
+    /// The server's clock is ahead of the system clock.
+    Ahead(Duration),
+    /// The server's clock is behind the system clock.
+    Behind(Duration),
+}
+
+impl ClockSkew {
+    /// `None` when the skewed time can not be represented by a [`SystemTime`].
+    fn apply(&self, time: SystemTime) -> Option<SystemTime> {
+        match *self {
+            ClockSkew::None => Some(time),
+            ClockSkew::Ahead(d) => time.checked_add(d),
+            ClockSkew::Behind(d) => time.checked_sub(d),
+        }
+    }
+}
+
+impl GrpcServer {
+    /// Skew the time given to generated responses (see [`Then::return_body_with`](crate::Then::return_body_with)),
+    /// eg. to test how a client tolerates tokens issued by a server with a different clock.
+    ///
+    /// ## Panics
+    /// * When the skewed time can not be represented by a [`SystemTime`]
+    pub fn set_clock_skew(&self, skew: ClockSkew) {
+        if skew.apply(SystemTime::now()).is_none() {
+            panic!("The clock skew {:?} is out of range.", skew);
+        }
+
+        *self.clock_skew.write().unwrap() = skew;
+    }
+
+    /// The current time of the server, including the clock skew.
+    pub fn now(&self) -> SystemTime {
+        now(&self.clock_skew)
+    }
+}
+
+pub(crate) fn now(skew: &RwLock<ClockSkew>) -> SystemTime {
+    let now = SystemTime::now();
+    skew.read().unwrap().apply(now).unwrap_or(now)
+}
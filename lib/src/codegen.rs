@@ -19,10 +19,6 @@
 /// // ... Later in your test (MyMockServer is generated above)
 /// let mut server = MyMockServer::start_default().await;
 /// ```
-/// 
-
-
-
 #[macro_export]
 macro_rules! generate {
     ($prefix:literal, $type: ident) => {
@@ -114,14 +110,26 @@ macro_rules! generate {
                 Self(GrpcServer::new(port)).start_internal().await
             }
 
+            /// Start the server on the given [`ServerRuntime`] and listens to an available port.
+            ///
+            /// ```no_run
+            /// let server = MyMockServer::start_with_runtime(ServerRuntime::Dedicated).await;
+            /// ```
+            pub async fn start_with_runtime(runtime: ServerRuntime) -> Self {
+                let port = GrpcServer::find_unused_port()
+                    .await
+                    .expect("Unable to find an open port");
+
+                Self(GrpcServer::new(port).with_runtime(runtime))
+                    .start_internal()
+                    .await
+            }
+
             async fn start_internal(&mut self) -> Self {
-                let address = self.address().clone();
-                let thread = tokio::spawn(
-                    tonic::transport::Server::builder()
-                        .add_service(self.clone())
-                        .serve(address),
-                );
-                self._start(thread).await;
+                let server = tonic::transport::Server::builder()
+                    .add_service(self.clone())
+                    .serve_with_incoming(self._incoming());
+                self._serve(server).await;
                 self.to_owned()
             }
         }
@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    task::{Context, Waker},
+    time::Duration,
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+/// Ordering anomalies applied to the messages of a streaming response.
+///
+/// The same `seed` always produces the same anomaly, so a failing test can be replayed.
+/// ```no_run
+/// server.setup(
+///     MockBuilder::given("/hello.Greeter/SayHelloStream")
+///         .return_stream(|| replies.clone())
+///         .with_stream_fault(StreamFault::Reorder { seed: 42 }),
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFault {
+    /// Send the messages in a shuffled order.
+    Reorder { seed: u64 },
+    /// Send some of the messages twice, the copy right after the original.
+    Duplicate { seed: u64 },
+}
+
+impl StreamFault {
+    pub(crate) fn apply(&self, mut messages: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+        match *self {
+            StreamFault::Reorder { seed } => {
+                messages.shuffle(&mut StdRng::seed_from_u64(seed));
+                messages
+            }
+            StreamFault::Duplicate { seed } => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let mut result = Vec::with_capacity(messages.len() * 2);
+                for m in messages {
+                    if rng.gen_bool(0.5) {
+                        result.push(m.clone());
+                    }
+                    result.push(m);
+                }
+                result
+            }
+        }
+    }
+}
+
+/// A fault applied to every request received on the connections of a [`ConnectionScope`],
+/// see [`GrpcServer::add_connection_fault`](crate::GrpcServer::add_connection_fault).
+///
+/// Sending a GOAWAY is not supported: tonic's server does not give access to the HTTP/2
+/// connection a request was received on, use [`ConnectionFault::Reset`] instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionFault {
+    /// Wait before handling the request.
+    Delay(Duration),
+    /// Reset the TCP connection instead of responding, the client has to open a new one.
+    /// The request is not recorded.
+    Reset,
+}
+
+/// The connections a [`ConnectionFault`] applies to.
+///
+/// Connections are numbered from `0`, in the order their first request is received.
+#[derive(Clone)]
+pub enum ConnectionScope {
+    /// The `n`th connection.
+    Nth(usize),
+    /// Connections for which the predicate on the remote address returns `true`.
+    Matching(Arc<dyn Fn(&SocketAddr) -> bool + Send + Sync>),
+}
+
+impl ConnectionScope {
+    /// Connections for which the predicate on the remote address returns `true`.
+    pub fn matching<F>(f: F) -> Self
+    where
+        F: Fn(&SocketAddr) -> bool + Send + Sync + 'static,
+    {
+        ConnectionScope::Matching(Arc::new(f))
+    }
+
+    fn contains(&self, index: usize, remote_addr: &SocketAddr) -> bool {
+        match self {
+            ConnectionScope::Nth(n) => *n == index,
+            ConnectionScope::Matching(f) => f(remote_addr),
+        }
+    }
+}
+
+impl Debug for ConnectionScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionScope::Nth(n) => f.debug_tuple("Nth").field(n).finish(),
+            ConnectionScope::Matching(_) => f.write_str("Matching"),
+        }
+    }
+}
+
+/// Resets a connection accepted by the server, see [`ConnectionFault::Reset`].
+#[derive(Debug, Default)]
+pub(crate) struct ResetSwitch {
+    reset: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl ResetSwitch {
+    pub(crate) fn trigger(&self) {
+        self.reset.store(true, Ordering::SeqCst);
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+
+    /// Returns `true` once triggered, otherwise the task of `cx` is woken up when it is.
+    pub(crate) fn poll(&self, cx: &Context<'_>) -> bool {
+        *self.waker.lock().unwrap() = Some(cx.waker().clone());
+        self.reset.load(Ordering::SeqCst)
+    }
+}
+
+/// Connections seen by the server and the faults registered for them.
+#[derive(Debug, Default)]
+pub(crate) struct ConnectionFaults {
+    connections: Vec<SocketAddr>,
+    open: HashMap<SocketAddr, Weak<ResetSwitch>>,
+    faults: Vec<(ConnectionScope, ConnectionFault)>,
+}
+
+impl ConnectionFaults {
+    pub(crate) fn add(&mut self, scope: ConnectionScope, fault: ConnectionFault) {
+        self.faults.push((scope, fault));
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.faults.clear();
+    }
+
+    /// Keep the switch to reset a new connection.
+    pub(crate) fn accepted(&mut self, remote_addr: SocketAddr, reset: &Arc<ResetSwitch>) {
+        self.open.retain(|_, r| r.strong_count() > 0);
+        self.open.insert(remote_addr, Arc::downgrade(reset));
+    }
+
+    /// Reset the connection from the given remote address, if it is still open.
+    pub(crate) fn reset(&self, remote_addr: &SocketAddr) {
+        if let Some(reset) = self.open.get(remote_addr).and_then(Weak::upgrade) {
+            reset.trigger();
+        }
+    }
+
+    /// Faults to apply to a request from the given remote address.
+    pub(crate) fn faults_for(&mut self, remote_addr: SocketAddr) -> Vec<ConnectionFault> {
+        let index = match self.connections.iter().position(|c| *c == remote_addr) {
+            Some(index) => index,
+            None => {
+                self.connections.push(remote_addr);
+                self.connections.len() - 1
+            }
+        };
+
+        self.faults
+            .iter()
+            .filter(|(scope, _)| scope.contains(index, &remote_addr))
+            .map(|(_, fault)| *fault)
+            .collect()
+    }
+}
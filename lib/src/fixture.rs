@@ -0,0 +1,46 @@
+use std::{fs, path::Path};
+
+use prost::bytes::Buf;
+
+/// Read messages from a newline-delimited JSON file, each (non empty) line is mapped to a message by `f`.
+pub(crate) fn read_ndjson<T, F>(path: &Path, f: F) -> Vec<Vec<u8>>
+where
+    F: Fn(serde_json::Value) -> T,
+    T: prost::Message,
+{
+    let content = fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("Unable to read {}: {}", path.display(), e));
+
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let value = serde_json::from_str(line).unwrap_or_else(|e| {
+                panic!("Invalid JSON in {} line {}: {}", path.display(), i + 1, e)
+            });
+            f(value).encode_to_vec()
+        })
+        .collect()
+}
+
+/// Read messages from a binary file where each message is prefixed by its length as a varint,
+/// (eg. written using [`prost::Message::encode_length_delimited`]).
+pub(crate) fn read_length_delimited(path: &Path) -> Vec<Vec<u8>> {
+    let content =
+        fs::read(path).unwrap_or_else(|e| panic!("Unable to read {}: {}", path.display(), e));
+
+    let mut buf = &content[..];
+    let mut messages = Vec::default();
+    while buf.has_remaining() {
+        let len = prost::encoding::decode_varint(&mut buf)
+            .unwrap_or_else(|e| panic!("Invalid length prefix in {}: {}", path.display(), e))
+            as usize;
+        if len > buf.remaining() {
+            panic!("Truncated message in {}", path.display());
+        }
+        messages.push(buf[..len].to_vec());
+        buf.advance(len);
+    }
+    messages
+}
@@ -1,22 +1,33 @@
 use log::{debug, info, warn};
 use std::{
+    future::Future,
     net::{SocketAddr, TcpStream},
     sync::{Arc, RwLock},
     time::Duration,
 };
 
-use crate::tonic_ext::{GenericCodec, GenericSvc};
-use crate::MockBuilder;
+use crate::clock::{self, ClockSkew};
+use crate::fault::ConnectionFaults;
+use crate::maintenance::maintenance_status;
+use crate::matcher::MatcherRegistry;
+use crate::runtime::{ServerRuntime, Worker};
+use crate::tonic_ext::{GenericCodec, GenericSvc, Incoming};
+use crate::{ConnectionFault, ConnectionScope, MockBuilder};
 use rand::Rng;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    time::Instant,
+};
+use tokio_stream::Stream;
 use tonic::{
     codegen::{
-        http::{self, HeaderMap, HeaderValue, Method},
-        Body,  StdError,
+        http::{self, HeaderMap, Method},
+        Body, StdError,
     },
-    Code,
+    transport::server::{Connected, TcpConnectInfo},
+    Code, Status,
 };
 
-
 /// A running gRPC server
 /// You do not directly create this object instead use the
 /// macro generated server to instantiate this for you.
@@ -33,11 +44,16 @@ pub struct GrpcServer {
     pub(crate) address: SocketAddr,
     inner: Arc<Option<Inner>>,
     pub(crate) rules: Arc<RwLock<Vec<RuleItem>>>,
+    pub(crate) maintenance: Arc<RwLock<Option<Instant>>>,
+    pub(crate) matcher_registry: Arc<RwLock<MatcherRegistry>>,
+    pub(crate) clock_skew: Arc<RwLock<ClockSkew>>,
+    connection_faults: Arc<RwLock<ConnectionFaults>>,
+    runtime: ServerRuntime,
 }
 
 #[derive(Debug)]
 pub(crate) struct RuleItem {
-    pub(crate) rule: MockBuilder,
+    pub(crate) rule: Arc<MockBuilder>,
 
     pub(crate) invocations_count: u32,
     pub(crate) invocations: Vec<RequestItem>,
@@ -49,20 +65,14 @@ pub struct RequestItem {
     pub headers: HeaderMap,
     pub method: Method,
     pub uri: String,
+    /// Follows the clock of the server's runtime, so it is paused with `tokio::time::pause`.
+    pub received_at: Instant,
 }
 
 impl RuleItem {
-    fn record_request<B>(&mut self, r: &http::Request<B>)
-    where
-        B: Body + Send + 'static,
-        B::Error: Into<StdError> + Send + 'static,
-    {
+    fn record_request(&mut self, r: RequestItem) {
         self.invocations_count += 1;
-        self.invocations.push(RequestItem {
-            headers: r.headers().clone(),
-            method: r.method().clone(),
-            uri: r.uri().to_string(),
-        });
+        self.invocations.push(r);
     }
 }
 
@@ -70,6 +80,8 @@ impl RuleItem {
 struct Inner {
     #[allow(dead_code)]
     server_handle: tokio::task::JoinHandle<Result<(), tonic::transport::Error>>,
+    #[allow(dead_code)]
+    worker: Option<Worker>,
 }
 
 impl Drop for GrpcServer {
@@ -105,9 +117,20 @@ impl GrpcServer {
             address: format!("[::1]:{}", port).parse().unwrap(),
             inner: Arc::default(),
             rules: Arc::default(),
+            maintenance: Arc::default(),
+            matcher_registry: Arc::default(),
+            clock_skew: Arc::default(),
+            connection_faults: Arc::default(),
+            runtime: ServerRuntime::default(),
         }
     }
 
+    /// Set where the server runs once started, see [`ServerRuntime`].
+    pub fn with_runtime(mut self, runtime: ServerRuntime) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
     pub async fn find_unused_port() -> Option<u16> {
         let mut rng = rand::thread_rng();
 
@@ -115,7 +138,8 @@ impl GrpcServer {
             let port: u16 = rng.gen_range(50000..60000);
             let addr: SocketAddr = format!("[::1]:{}", port).parse().unwrap();
 
-            if TcpStream::connect_timeout(&addr, std::time::Duration::from_millis(25)).is_err() {
+            // a port can be in use without anyone listening, eg. by the client side of a connection
+            if std::net::TcpListener::bind(addr).is_ok() {
                 return Some(port);
             }
             tokio::time::sleep(Duration::from_millis(25)).await;
@@ -126,21 +150,57 @@ impl GrpcServer {
         &mut self,
         f: tokio::task::JoinHandle<Result<(), tonic::transport::Error>>,
     ) {
-        info!("Starting gRPC started in {}", self.address());
+        self.start_with_worker(f, None).await
+    }
 
-        let thread = f;
+    /// Spawn the server future onto the configured [`ServerRuntime`] and wait for it to start.
+    pub async fn _serve<F>(&mut self, f: F)
+    where
+        F: Future<Output = Result<(), tonic::transport::Error>> + Send + 'static,
+    {
+        let (thread, worker) = self.runtime.spawn(f);
 
-        for _ in 0..40 {
-            if TcpStream::connect_timeout(&self.address, std::time::Duration::from_millis(25))
-                .is_ok()
-            {
-                break;
+        self.start_with_worker(thread, worker).await
+    }
+
+    /// Listen to the address of the server, for [`tonic::transport::Server::serve_with_incoming`].
+    ///
+    /// ## Panics
+    /// * When the address is not available.
+    pub fn _incoming(
+        &self,
+    ) -> impl Stream<
+        Item = std::io::Result<
+            impl AsyncRead + AsyncWrite + Connected<ConnectInfo = TcpConnectInfo> + Unpin + Send,
+        >,
+    > {
+        Incoming::bind(self.address, self.connection_faults.clone())
+    }
+
+    async fn start_with_worker(
+        &mut self,
+        thread: tokio::task::JoinHandle<Result<(), tonic::transport::Error>>,
+        worker: Option<Worker>,
+    ) {
+        info!("Starting gRPC started in {}", self.address());
+
+        // wait in real time, the clock of the caller's runtime might be paused
+        let address = self.address;
+        let _ = tokio::task::spawn_blocking(move || {
+            for _ in 0..40 {
+                if TcpStream::connect_timeout(&address, std::time::Duration::from_millis(25))
+                    .is_ok()
+                {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(25));
             }
-            tokio::time::sleep(Duration::from_millis(25)).await;
-        }
+        })
+        .await;
 
         self.inner = Arc::new(Some(Inner {
             server_handle: thread,
+            worker,
         }));
 
         info!("Server started in {}", self.address());
@@ -160,10 +220,26 @@ impl GrpcServer {
         self.rules.write().unwrap().clear();
     }
 
+    /// Apply a fault to every request received on the connections in `scope`.
+    /// Faults are applied in the order they were added.
+    /// ```no_run
+    /// // the client must fail over to another connection
+    /// server.add_connection_fault(ConnectionScope::Nth(0), ConnectionFault::Reset);
+    /// ```
+    pub fn add_connection_fault(&self, scope: ConnectionScope, fault: ConnectionFault) {
+        self.connection_faults.write().unwrap().add(scope, fault);
+    }
+
+    /// Remove all faults added with [`GrpcServer::add_connection_fault`].
+    pub fn clear_connection_faults(&self) {
+        self.connection_faults.write().unwrap().clear();
+    }
+
     pub fn address(&self) -> &SocketAddr {
         &self.address
     }
 
+    #[allow(clippy::result_large_err)]
     pub fn handle_request<B>(
         &self,
         req: http::Request<B>,
@@ -174,55 +250,125 @@ impl GrpcServer {
     {
         info!("Request to {}", req.uri().path());
 
-        let path = req.uri().path();
-        let mut inner = self.rules.write().unwrap();
-
-        if let Some(item) = inner.iter_mut().find(|x| x.rule.path == path) {
-            info!("Matched rule {:?}", item);
-            item.record_request(&req);
-
-            let code = item.rule.status_code.unwrap_or(Code::Ok);
-            if let Some(body) = &item.rule.result {
-                debug!("Returning body ({} bytes)", body.len());
-                let body = body.clone();
-
-                let fut = async move {
-                    let method = GenericSvc(body);
-                    let codec = GenericCodec::default();
-
-                    let mut grpc = tonic::server::Grpc::new(codec);
-                    let mut result = grpc.unary(method, req).await;
-                    result.headers_mut().append(
-                        "grpc-status",
-                        HeaderValue::from_str(format!("{}", code as u32).as_str()).unwrap(),
-                    );
-                    Ok(result)
-                };
-                return Box::pin(fut);
-            } else {
-                let status = code as u32;
-                let builder = http::Response::builder()
-                    .status(200)
-                    .header("content-type", "application/grpc")
-                    .header("grpc-status", format!("{}", status));
-                info!("Returning empty body with status {}", status);
-
-                return Box::pin(async move {
-                    let body = builder.body(tonic::body::empty_body()).unwrap();
-                    Ok(body)
-                });
-            };
+        let path = req.uri().path().to_string();
+        let request_item = RequestItem {
+            headers: req.headers().clone(),
+            method: req.method().clone(),
+            uri: req.uri().to_string(),
+            received_at: Instant::now(),
+        };
+        let rules = self.rules.clone();
+        let maintenance = self.maintenance.clone();
+        let clock_skew = self.clock_skew.clone();
+        let connection_faults = self.connection_faults.clone();
+        let remote_addr = req
+            .extensions()
+            .get::<TcpConnectInfo>()
+            .and_then(|i| i.remote_addr());
+        let faults = remote_addr
+            .map(|addr| connection_faults.write().unwrap().faults_for(addr))
+            .unwrap_or_default();
+
+        Box::pin(async move {
+            for fault in faults {
+                match fault {
+                    ConnectionFault::Delay(d) => {
+                        info!("Delaying the request by {:?}", d);
+                        tokio::time::sleep(d).await;
+                    }
+                    ConnectionFault::Reset => {
+                        info!("Resetting the connection");
+                        if let Some(addr) = &remote_addr {
+                            connection_faults.read().unwrap().reset(addr);
+                        }
+                        // never sent, the connection is reset
+                        return Ok(Status::unavailable("connection reset").to_http());
+                    }
+                }
+            }
+
+            // `Grpc` decodes the body and hands a `tonic::Request` to the matchers
+            let method = GenericSvc(move |r: tonic::Request<Vec<u8>>| {
+                Self::respond(
+                    &rules,
+                    &maintenance,
+                    &clock_skew,
+                    &path,
+                    request_item.clone(),
+                    &r,
+                )
+            });
+            let codec = GenericCodec;
+
+            let mut grpc = tonic::server::Grpc::new(codec);
+            Ok(grpc.server_streaming(method, req).await)
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn respond(
+        rules: &RwLock<Vec<RuleItem>>,
+        maintenance: &RwLock<Option<Instant>>,
+        clock_skew: &RwLock<ClockSkew>,
+        path: &str,
+        request_item: RequestItem,
+        req: &tonic::Request<Vec<u8>>,
+    ) -> Result<tonic::Response<Vec<Vec<u8>>>, Status> {
+        // user matchers and responders run without holding the lock, so one that panics
+        // does not poison the rules for every other request
+        let candidates = rules
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|x| x.rule.path == path)
+            .map(|x| x.rule.clone())
+            .collect::<Vec<Arc<MockBuilder>>>();
+
+        if let Some(rule) = candidates
+            .into_iter()
+            .find(|rule| rule.matchers.iter().all(|m| m.matches(req)))
+        {
+            info!("Matched rule {:?}", rule);
+            if let Some(item) = rules
+                .write()
+                .unwrap()
+                .iter_mut()
+                .find(|x| Arc::ptr_eq(&x.rule, &rule))
+            {
+                item.record_request(request_item);
+            }
+
+            if let Some(status) = maintenance_status(maintenance) {
+                info!("Returning status {} (maintenance)", status.code() as u32);
+                return Err(status);
+            }
+
+            let code = rule.status_code.unwrap_or(Code::Ok);
+            if code != Code::Ok {
+                info!("Returning status {}", code as u32);
+                return Err(Status::new(code, ""));
+            }
+
+            if let Some(responder) = &rule.responder {
+                let body = (responder.0)(req, clock::now(clock_skew))?;
+                debug!("Returning generated body ({} bytes)", body.len());
+                return Ok(tonic::Response::new(vec![body]));
+            }
+
+            if let Some(mut messages) = rule.stream.clone() {
+                if let Some(fault) = &rule.stream_fault {
+                    messages = fault.apply(messages);
+                }
+                debug!("Returning stream ({} messages)", messages.len());
+                return Ok(tonic::Response::new(messages));
+            }
+
+            let body = rule.result.clone().unwrap_or_default();
+            debug!("Returning body ({} bytes)", body.len());
+            return Ok(tonic::Response::new(vec![body]));
         }
 
         warn!("Request unhandled");
-        let builder = http::Response::builder()
-            .status(200)
-            .header("content-type", "application/grpc")
-            .header("grpc-status", format!("{}", Code::Unimplemented as u32));
-
-        return Box::pin(async move {
-            let body = builder.body(tonic::body::empty_body()).unwrap();
-            Ok(body)
-        });
+        Err(Status::new(Code::Unimplemented, ""))
     }
 }
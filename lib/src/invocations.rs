@@ -1,6 +1,75 @@
-use crate::{grpc_server::RequestItem, GrpcServer, MockBuilder};
+use std::{sync::Arc, time::Duration};
+
+use tonic::Code;
+
+use crate::{grpc_server::RequestItem, GrpcServer, MockBuilder, StreamFault};
+
+/// Description of a rule registered with the server, see [`GrpcServer::stubs`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StubDescription {
+    pub path: String,
+    /// Descriptions of the matchers other than the path (see [`Matcher::describe`](crate::Matcher::describe)).
+    pub matchers: Vec<String>,
+    pub response: ResponseKind,
+    /// Position of the rule in the evaluation order, a request is handled by the first rule that matches.
+    pub priority: usize,
+    pub hit_count: u32,
+    pub tags: Vec<String>,
+}
+
+/// What a rule responds with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseKind {
+    /// A status without message.
+    Status(Code),
+    /// A single message.
+    Body { len: usize },
+    /// A stream of messages.
+    Stream {
+        messages: usize,
+        fault: Option<StreamFault>,
+    },
+    /// A message created for each request (eg. [`Pagination`](crate::Pagination)).
+    Generated,
+}
+
+impl From<&MockBuilder> for ResponseKind {
+    fn from(rule: &MockBuilder) -> Self {
+        match rule.status_code.unwrap_or(Code::Ok) {
+            Code::Ok if rule.responder.is_some() => ResponseKind::Generated,
+            Code::Ok => match &rule.stream {
+                Some(stream) => ResponseKind::Stream {
+                    messages: stream.len(),
+                    fault: rule.stream_fault,
+                },
+                None => ResponseKind::Body {
+                    len: rule.result.as_ref().map(|r| r.len()).unwrap_or_default(),
+                },
+            },
+            code => ResponseKind::Status(code),
+        }
+    }
+}
 
 impl GrpcServer {
+    /// Describe the rules registered with the server, in evaluation order.
+    pub fn stubs(&self) -> Vec<StubDescription> {
+        self.rules
+            .read()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(priority, item)| StubDescription {
+                path: item.rule.path.clone(),
+                matchers: item.rule.matchers.iter().map(|m| m.describe()).collect(),
+                response: ResponseKind::from(item.rule.as_ref()),
+                priority,
+                hit_count: item.invocations_count,
+                tags: item.rule.tags.clone(),
+            })
+            .collect()
+    }
+
     /// Finds one or more matched requests for a given request builder.
     ///
     /// ## Returns
@@ -8,7 +77,7 @@ impl GrpcServer {
     /// * Empty Vector: when no request was made that matches the builder,
     pub fn find(&self, r: &MockBuilder) -> Option<Vec<RequestItem>> {
         for item in self.rules.read().unwrap().iter() {
-            if &item.rule == r {
+            if item.rule.as_ref() == r {
                 let mut result = Vec::default();
                 for i in &item.invocations {
                     result.push(i.clone());
@@ -39,6 +108,57 @@ impl GrpcServer {
         }
     }
 
+    /// Finds all requests handled by rules with the given tag (see [`MockBuilder::with_tag`]),
+    /// in the order the rules were registered.
+    pub fn calls_with_tag(&self, tag: &str) -> Vec<RequestItem> {
+        self.rules
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|item| item.rule.tags.iter().any(|t| t == tag))
+            .flat_map(|item| item.invocations.iter().cloned())
+            .collect()
+    }
+
+    /// Asserts that consecutive requests to the given path were received at least `gap` apart,
+    /// eg. to verify that a client backs off between retries.
+    ///
+    /// ## Panics
+    /// * When less than two requests to the path were handled
+    /// * When two consecutive requests were received less than `gap` apart
+    pub fn assert_client_retry_gap_at_least(&self, path: &str, gap: Duration) {
+        let mut received = self
+            .rules
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|item| item.rule.path == path)
+            .flat_map(|item| item.invocations.iter().map(|i| i.received_at))
+            .collect::<Vec<_>>();
+        received.sort();
+
+        if received.len() < 2 {
+            panic!(
+                "Expected retries to {} but {} request(s) received.",
+                path,
+                received.len()
+            );
+        }
+
+        for (i, w) in received.windows(2).enumerate() {
+            let actual = w[1] - w[0];
+            if actual < gap {
+                panic!(
+                    "Request {} to {} was received {:?} after the previous one, expected at least {:?}.",
+                    i + 2,
+                    path,
+                    actual,
+                    gap
+                );
+            }
+        }
+    }
+
     /// Returns number of handled requests
     pub fn find_request_count(&self) -> u32 {
         let mut count = 0;
@@ -67,7 +187,21 @@ impl GrpcServer {
 impl PartialEq for MockBuilder {
     fn eq(&self, other: &Self) -> bool {
         self.path == other.path
+            && self.matchers.len() == other.matchers.len()
+            && self
+                .matchers
+                .iter()
+                .zip(other.matchers.iter())
+                .all(|(a, b)| Arc::ptr_eq(a, b))
             && self.status_code == other.status_code
             && self.result == other.result
+            && self.stream == other.stream
+            && self.stream_fault == other.stream_fault
+            && match (&self.responder, &other.responder) {
+                (Some(a), Some(b)) => Arc::ptr_eq(&a.0, &b.0),
+                (a, b) => a.is_none() && b.is_none(),
+            }
+            && self.tags == other.tags
+            && self.custom_matcher == other.custom_matcher
     }
 }
@@ -1,12 +1,28 @@
 #![doc = include_str!("../README.md")]
 mod builder;
+mod clock;
 mod codegen;
+mod fault;
+mod fixture;
 mod grpc_server;
 mod invocations;
+mod maintenance;
+mod mappings;
+mod matcher;
+mod pagination;
+mod runtime;
 mod tonic_ext;
 
 pub use builder::{MockBuilder, Mountable, Then};
+pub use clock::ClockSkew;
+pub use fault::{ConnectionFault, ConnectionScope, StreamFault};
 pub use grpc_server::GrpcServer;
+pub use invocations::{ResponseKind, StubDescription};
+pub use mappings::StubMappingError;
+pub use matcher::Matcher;
+pub use pagination::Pagination;
+pub use runtime::ServerRuntime;
 
 pub extern crate http_body;
+pub extern crate serde_json;
 pub extern crate tonic;
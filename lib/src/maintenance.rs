@@ -0,0 +1,82 @@
+use std::{sync::RwLock, time::Duration};
+
+use prost::Message;
+use tokio::time::Instant;
+use tonic::{Code, Status};
+
+use crate::GrpcServer;
+
+/// `google.rpc.Status`, sent in the `grpc-status-details-bin` trailer.
+#[derive(Clone, PartialEq, Message)]
+struct StatusDetails {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<prost_types::Any>,
+}
+
+/// `google.rpc.RetryInfo`
+#[derive(Clone, PartialEq, Message)]
+struct RetryInfo {
+    #[prost(message, optional, tag = "1")]
+    retry_delay: Option<prost_types::Duration>,
+}
+
+impl GrpcServer {
+    /// Start a maintenance window: every rule answers `Unavailable` until `duration` elapsed,
+    /// then the server goes back to normal by itself. The window follows the tokio clock,
+    /// so it ends when a paused clock is advanced past it.
+    ///
+    /// The status carries a `google.rpc.RetryInfo` detail with the time left in the window.
+    /// Requests are still recorded against the rules they match.
+    pub fn start_maintenance(&self, duration: Duration) {
+        *self.maintenance.write().unwrap() = Some(Instant::now() + duration);
+    }
+
+    /// End the maintenance window started with [`GrpcServer::start_maintenance`] early.
+    pub fn stop_maintenance(&self) {
+        *self.maintenance.write().unwrap() = None;
+    }
+
+    /// Returns `true` while a maintenance window is ongoing.
+    pub fn in_maintenance(&self) -> bool {
+        maintenance_status(&self.maintenance).is_some()
+    }
+}
+
+/// The status to answer with when a maintenance window is ongoing.
+pub(crate) fn maintenance_status(maintenance: &RwLock<Option<Instant>>) -> Option<Status> {
+    let mut maintenance = maintenance.write().unwrap();
+    let until = (*maintenance)?;
+
+    let now = Instant::now();
+    if now >= until {
+        *maintenance = None;
+        return None;
+    }
+
+    let retry_delay = until - now;
+    let retry_info = RetryInfo {
+        retry_delay: Some(prost_types::Duration {
+            seconds: retry_delay.as_secs() as i64,
+            nanos: retry_delay.subsec_nanos() as i32,
+        }),
+    };
+    let message = format!("Under maintenance, retry in {:?}", retry_delay);
+    let details = StatusDetails {
+        code: Code::Unavailable as i32,
+        message: message.clone(),
+        details: vec![prost_types::Any {
+            type_url: "type.googleapis.com/google.rpc.RetryInfo".into(),
+            value: retry_info.encode_to_vec(),
+        }],
+    };
+
+    Some(Status::with_details(
+        Code::Unavailable,
+        message,
+        details.encode_to_vec().into(),
+    ))
+}
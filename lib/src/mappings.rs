@@ -0,0 +1,255 @@
+use std::{collections::HashMap, fmt::Display, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use tonic::Code;
+
+use crate::{
+    matcher::{CustomMatcher, MatcherRegistry},
+    GrpcServer, Matcher, MockBuilder, Mountable,
+};
+
+/// Stub mappings in the WireMock JSON format.
+/// ```json
+/// {
+///   "mappings": [
+///     {
+///       "request": {
+///         "urlPath": "/hello.Greeter/SayHello",
+///         "customMatcher": { "name": "tenant", "parameters": { "id": "acme" } }
+///       },
+///       "response": {
+///         "headers": { "grpc-status": "0" },
+///         "base64Body": "Cg5IZWxsbyBNdXN0YWtpbQ=="
+///       },
+///       "metadata": { "tags": ["critical-path"] }
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Mappings {
+    mappings: Vec<Mapping>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Mapping {
+    request: MappingRequest,
+    response: MappingResponse,
+    #[serde(default, skip_serializing_if = "MappingMetadata::is_empty")]
+    metadata: MappingMetadata,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MappingRequest {
+    url_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    custom_matcher: Option<CustomMatcher>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MappingResponse {
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    headers: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base64_body: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MappingMetadata {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+impl MappingMetadata {
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+}
+
+/// Why stub mappings could not be imported or exported.
+#[derive(Debug)]
+pub enum StubMappingError {
+    /// The document is not valid JSON or not in the WireMock format.
+    Json(serde_json::Error),
+    /// The `grpc-status` header is not a gRPC status code (`0` to `16`).
+    InvalidStatus(String),
+    /// The `base64Body` is not valid base64.
+    InvalidBody(base64::DecodeError),
+    /// The `customMatcher` is not registered with [`GrpcServer::register_matcher`].
+    UnknownMatcher(String),
+    /// The rule for `path` can not be serialized, eg. it has a streaming response.
+    Unsupported { path: String, reason: &'static str },
+}
+
+impl Display for StubMappingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StubMappingError::Json(e) => write!(f, "Invalid stub mappings: {}", e),
+            StubMappingError::InvalidStatus(s) => write!(f, "Invalid grpc-status `{}`", s),
+            StubMappingError::InvalidBody(e) => write!(f, "Invalid base64Body: {}", e),
+            StubMappingError::UnknownMatcher(name) => write!(
+                f,
+                "Unknown custom matcher `{}`, it must be registered using `register_matcher()`",
+                name
+            ),
+            StubMappingError::Unsupported { path, reason } => {
+                write!(f, "Unable to export the rule for {}, {}", path, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StubMappingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StubMappingError::Json(e) => Some(e),
+            StubMappingError::InvalidBody(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<&MockBuilder> for Mapping {
+    type Error = StubMappingError;
+
+    fn try_from(rule: &MockBuilder) -> Result<Self, Self::Error> {
+        let unsupported = |reason| StubMappingError::Unsupported {
+            path: rule.path.clone(),
+            reason,
+        };
+        if rule.matchers.len() > rule.custom_matcher.iter().count() {
+            return Err(unsupported(
+                "matchers not registered with `register_matcher()` can not be serialized",
+            ));
+        }
+        if rule.stream.is_some() || rule.responder.is_some() {
+            return Err(unsupported(
+                "streaming or generated responses can not be serialized",
+            ));
+        }
+
+        let mut response = MappingResponse::default();
+        if let Some(code) = rule.status_code {
+            response
+                .headers
+                .insert("grpc-status".into(), format!("{}", code as i32));
+        }
+        response.base64_body = rule.result.as_ref().map(base64::encode);
+
+        Ok(Self {
+            request: MappingRequest {
+                url_path: rule.path.clone(),
+                custom_matcher: rule.custom_matcher.clone(),
+            },
+            response,
+            metadata: MappingMetadata {
+                tags: rule.tags.clone(),
+            },
+        })
+    }
+}
+
+impl Mapping {
+    fn into_rule(self, registry: &MatcherRegistry) -> Result<MockBuilder, StubMappingError> {
+        let status_code = match self.response.headers.get("grpc-status") {
+            // `Code::from_i32` maps anything out of range to `Unknown`
+            Some(s) => s
+                .parse::<i32>()
+                .ok()
+                .filter(|code| (0..=16).contains(code))
+                .map(Code::from_i32)
+                .ok_or_else(|| StubMappingError::InvalidStatus(s.clone()))?,
+            None => Code::Ok,
+        };
+        let result = match self.response.base64_body {
+            Some(b) => Some(base64::decode(b).map_err(StubMappingError::InvalidBody)?),
+            None => None,
+        };
+
+        let mut matchers = Vec::default();
+        if let Some(custom) = &self.request.custom_matcher {
+            let matcher = registry
+                .resolve(custom)
+                .ok_or_else(|| StubMappingError::UnknownMatcher(custom.name.clone()))?;
+            matchers.push(matcher);
+        }
+
+        Ok(MockBuilder {
+            path: self.request.url_path,
+            matchers,
+            status_code: Some(status_code),
+            result,
+            stream: None,
+            stream_fault: None,
+            responder: None,
+            tags: self.metadata.tags,
+            custom_matcher: self.request.custom_matcher,
+        })
+    }
+}
+
+impl GrpcServer {
+    /// Register a named [`Matcher`] that stub mappings can refer to with `customMatcher`.
+    /// The factory creates the matcher from the `parameters` of the mapping.
+    /// ```no_run
+    /// server.register_matcher("tenant", |parameters: &serde_json::Value| {
+    ///     let id = parameters["id"].as_str().unwrap_or_default().to_string();
+    ///     move |req: &tonic::Request<Vec<u8>>| {
+    ///         req.metadata().get("x-tenant").map(|v| v == id.as_str()) == Some(true)
+    ///     }
+    /// });
+    /// ```
+    pub fn register_matcher<F, M>(&self, name: &str, factory: F)
+    where
+        F: Fn(&serde_json::Value) -> M + Send + Sync + 'static,
+        M: Matcher + 'static,
+    {
+        self.matcher_registry.write().unwrap().register(
+            name,
+            Arc::new(move |parameters| Arc::new(factory(parameters)) as Arc<dyn Matcher>),
+        );
+    }
+
+    /// Export all registered rules as stub mappings in the WireMock JSON format,
+    /// eg. to commit the setup of a mock as a fixture.
+    ///
+    /// ## Returns
+    /// The JSON document, or [`StubMappingError::Unsupported`] when a rule has a streaming or generated
+    /// response, or a [`Matcher`] that is not registered with [`GrpcServer::register_matcher`].
+    pub fn export_stubs(&self) -> Result<String, StubMappingError> {
+        let mappings = Mappings {
+            mappings: self
+                .rules
+                .read()
+                .unwrap()
+                .iter()
+                .map(|r| Mapping::try_from(r.rule.as_ref()))
+                .collect::<Result<_, _>>()?,
+        };
+
+        serde_json::to_string_pretty(&mappings).map_err(StubMappingError::Json)
+    }
+
+    /// Register all stub mappings from a WireMock JSON document (as produced by [`GrpcServer::export_stubs`]).
+    ///
+    /// ## Returns
+    /// Number of rules registered, or an error when the document is invalid (no rule is registered in that case).
+    pub fn import_stubs(&mut self, json: &str) -> Result<usize, StubMappingError> {
+        let mappings: Mappings = serde_json::from_str(json).map_err(StubMappingError::Json)?;
+        let registry = self.matcher_registry.read().unwrap().clone();
+        let rules = mappings
+            .mappings
+            .into_iter()
+            .map(|m| m.into_rule(&registry))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let count = rules.len();
+        for rule in rules {
+            rule.mount(self);
+        }
+
+        Ok(count)
+    }
+}
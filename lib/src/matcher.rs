@@ -0,0 +1,113 @@
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+/// A condition an incoming request must satisfy for a rule to handle it.
+///
+/// Before any matcher is evaluated, the raw `http::Request` is converted into a
+/// [`tonic::Request`] the same way a generated tonic server would do it: headers
+/// become the [`MetadataMap`](tonic::metadata::MetadataMap), extensions (eg. the
+/// remote address) are carried over and the gRPC frame is unwrapped. The message
+/// is the encoded protobuf payload, so it can be decoded into the expected type.
+///
+/// Any `Fn(&tonic::Request<Vec<u8>>) -> bool` closure is a matcher.
+/// ```no_run
+/// use prost::Message;
+///
+/// server.setup(
+///     MockBuilder::when()
+///         .path("/hello.Greeter/SayHello")
+///         .matching(|req: &tonic::Request<Vec<u8>>| {
+///             req.metadata().get("x-tenant").is_some()
+///                 && HelloRequest::decode(&req.get_ref()[..])
+///                     .map(|r| r.name == "Mustakim")
+///                     .unwrap_or(false)
+///         })
+///         .then()
+///         .return_status(Code::Ok),
+/// );
+/// ```
+pub trait Matcher: Send + Sync {
+    /// Returns `true` when the request should be handled by the rule.
+    fn matches(&self, request: &tonic::Request<Vec<u8>>) -> bool;
+
+    /// A short description of the matcher, used by [`GrpcServer::stubs`](crate::GrpcServer::stubs).
+    fn describe(&self) -> String {
+        "custom matcher".into()
+    }
+}
+
+impl<F> Matcher for F
+where
+    F: Fn(&tonic::Request<Vec<u8>>) -> bool + Send + Sync,
+{
+    fn matches(&self, request: &tonic::Request<Vec<u8>>) -> bool {
+        self(request)
+    }
+}
+
+impl Debug for dyn Matcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.describe())
+    }
+}
+
+/// A [`Matcher`] referenced by name from a stub mapping (see [`GrpcServer::register_matcher`](crate::GrpcServer::register_matcher)).
+/// ```json
+/// "customMatcher": { "name": "tenant", "parameters": { "id": "acme" } }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct CustomMatcher {
+    pub(crate) name: String,
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub(crate) parameters: serde_json::Value,
+}
+
+type MatcherFactory = Arc<dyn Fn(&serde_json::Value) -> Arc<dyn Matcher> + Send + Sync>;
+
+/// Named matcher factories, used to resolve [`CustomMatcher`]s.
+#[derive(Default, Clone)]
+pub(crate) struct MatcherRegistry {
+    factories: HashMap<String, MatcherFactory>,
+}
+
+impl MatcherRegistry {
+    pub(crate) fn register(&mut self, name: &str, factory: MatcherFactory) {
+        self.factories.insert(name.into(), factory);
+    }
+
+    pub(crate) fn resolve(&self, custom: &CustomMatcher) -> Option<Arc<dyn Matcher>> {
+        self.factories.get(&custom.name).map(|factory| {
+            Arc::new(NamedMatcher {
+                custom: custom.clone(),
+                inner: factory(&custom.parameters),
+            }) as Arc<dyn Matcher>
+        })
+    }
+}
+
+/// A matcher created from the registry, described by its name and parameters.
+struct NamedMatcher {
+    custom: CustomMatcher,
+    inner: Arc<dyn Matcher>,
+}
+
+impl Matcher for NamedMatcher {
+    fn matches(&self, request: &tonic::Request<Vec<u8>>) -> bool {
+        self.inner.matches(request)
+    }
+
+    fn describe(&self) -> String {
+        if self.custom.parameters.is_null() {
+            self.custom.name.clone()
+        } else {
+            format!("{} {}", self.custom.name, self.custom.parameters)
+        }
+    }
+}
+
+impl Debug for MatcherRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.factories.keys()).finish()
+    }
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use tonic::Status;
+
+use crate::builder::Responder;
+
+type PageToken = Arc<dyn Fn(&[u8]) -> Result<String, Status> + Send + Sync>;
+type PageResponse<T> = Arc<dyn Fn(Vec<T>, String) -> Vec<u8> + Send + Sync>;
+
+/// Serve a list of items one page at a time, for list RPCs.
+///
+/// The page token is the offset of the first item of the page, an empty token is the first page
+/// and an empty next page token is returned with the last page. A page token that was not
+/// produced by the mock is answered with `InvalidArgument`.
+/// ```no_run
+/// server.setup(
+///     MockBuilder::given("/hello.Greeter/ListGreetings").return_pages(
+///         Pagination::new(greetings, 2)
+///             .page_token(|req: ListGreetingsRequest| req.page_token)
+///             .response(|greetings, next_page_token| ListGreetingsReply {
+///                 greetings,
+///                 next_page_token,
+///             }),
+///     ),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct Pagination<T> {
+    items: Vec<T>,
+    page_size: usize,
+    page_token: Option<PageToken>,
+    response: Option<PageResponse<T>>,
+}
+
+impl<T> Pagination<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    /// ## Panics
+    /// * When `page_size` is `0`
+    pub fn new(items: Vec<T>, page_size: usize) -> Self {
+        if page_size == 0 {
+            panic!("The page size must be greater than 0.");
+        }
+
+        Self {
+            items,
+            page_size,
+            page_token: None,
+            response: None,
+        }
+    }
+
+    /// Read the page token from the request.
+    #[allow(clippy::result_large_err)]
+    pub fn page_token<R, F>(self, f: F) -> Self
+    where
+        R: prost::Message + Default,
+        F: Fn(R) -> String + Send + Sync + 'static,
+    {
+        let page_token: PageToken = Arc::new(move |body| {
+            R::decode(body)
+                .map(&f)
+                .map_err(|e| Status::invalid_argument(e.to_string()))
+        });
+
+        Self {
+            page_token: Some(page_token),
+            ..self
+        }
+    }
+
+    /// Create the response from the items of the page and the next page token.
+    pub fn response<M, F>(self, f: F) -> Self
+    where
+        M: prost::Message,
+        F: Fn(Vec<T>, String) -> M + Send + Sync + 'static,
+    {
+        let response: PageResponse<T> =
+            Arc::new(move |page, next_page_token| f(page, next_page_token).encode_to_vec());
+
+        Self {
+            response: Some(response),
+            ..self
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn into_responder(self) -> Responder {
+        let page_token = self
+            .page_token
+            .expect("You must set how to read the page token (eg. `.page_token(|req: ListRequest| req.page_token)`)");
+        let response = self
+            .response
+            .expect("You must set how to create the response (eg. `.response(|items, next_page_token| ListReply { .. })`)");
+        let items = self.items;
+        let page_size = self.page_size;
+
+        Responder(Arc::new(move |req, _| {
+            let token = page_token(req.get_ref())?;
+            let start = match token.as_str() {
+                "" => 0,
+                t => t
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|start| *start < items.len())
+                    .ok_or_else(|| {
+                        Status::invalid_argument(format!("Invalid page token `{}`", t))
+                    })?,
+            };
+            let end = (start + page_size).min(items.len());
+            let next_page_token = if end < items.len() {
+                end.to_string()
+            } else {
+                String::default()
+            };
+
+            Ok(response(items[start..end].to_vec(), next_page_token))
+        }))
+    }
+}
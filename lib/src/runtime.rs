@@ -0,0 +1,55 @@
+use std::future::Future;
+
+use tokio::{sync::oneshot, task::JoinHandle};
+
+/// Where the mock server accepts connections and handles requests.
+///
+/// Use anything other than [`ServerRuntime::Ambient`] when the test controls the
+/// runtime it is running on (eg. `tokio::time::pause`), so the server's timers and
+/// I/O are not affected by it.
+/// ```no_run
+/// let server = MyMockServer::start_with_runtime(ServerRuntime::Dedicated).await;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub enum ServerRuntime {
+    /// Spawn onto the runtime the server is started from.
+    #[default]
+    Ambient,
+    /// Run on a new current-thread runtime in its own thread.
+    /// The runtime is shut down when the server is dropped.
+    Dedicated,
+    /// Spawn onto the runtime of the given handle.
+    Handle(tokio::runtime::Handle),
+}
+
+/// Keeps a [`ServerRuntime::Dedicated`] runtime alive until dropped.
+#[derive(Debug)]
+pub(crate) struct Worker(#[allow(dead_code)] oneshot::Sender<()>);
+
+impl ServerRuntime {
+    pub(crate) fn spawn<F>(&self, f: F) -> (JoinHandle<F::Output>, Option<Worker>)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match self {
+            ServerRuntime::Ambient => (tokio::spawn(f), None),
+            ServerRuntime::Handle(handle) => (handle.spawn(f), None),
+            ServerRuntime::Dedicated => {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("Unable to build the server runtime");
+                let handle = runtime.spawn(f);
+
+                // tasks on a current-thread runtime only progress while it is blocked on
+                let (tx, rx) = oneshot::channel::<()>();
+                std::thread::spawn(move || {
+                    let _ = runtime.block_on(rx);
+                });
+
+                (handle, Some(Worker(tx)))
+            }
+        }
+    }
+}
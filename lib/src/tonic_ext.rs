@@ -1,14 +1,40 @@
-use prost::{bytes::BufMut, Message};
-
-use tonic::{codec::Codec, Code};
-
-pub(crate) struct GenericSvc(pub(crate) Vec<u8>);
-impl tonic::server::UnaryService<Vec<u8>> for GenericSvc {
+use std::{
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, RwLock},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use prost::bytes::{Buf, BufMut};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_stream::Stream;
+use tonic::{
+    codec::Codec,
+    transport::server::{Connected, TcpConnectInfo},
+};
+
+use crate::fault::{ConnectionFaults, ResetSwitch};
+
+/// Adapts a function over [`tonic::Request`] into a server streaming gRPC service, this lets
+/// [`tonic::server::Grpc`] turn the raw `http::Request` into a `tonic::Request`.
+///
+/// A unary response is the same as a stream of a single message on the wire,
+/// so this serves both kind of RPCs.
+pub(crate) struct GenericSvc<F>(pub(crate) F);
+impl<F> tonic::server::ServerStreamingService<Vec<u8>> for GenericSvc<F>
+where
+    F: FnMut(tonic::Request<Vec<u8>>) -> Result<tonic::Response<Vec<Vec<u8>>>, tonic::Status>,
+{
     type Response = Vec<u8>;
-    type Future = tonic::codegen::BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
-    fn call(&mut self, _: tonic::Request<Vec<u8>>) -> Self::Future {
-        let body = self.0.clone();
-        let fut = async move { Ok(tonic::Response::new(body)) };
+    type ResponseStream = tokio_stream::Iter<std::vec::IntoIter<Result<Vec<u8>, tonic::Status>>>;
+    type Future = tonic::codegen::BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+    fn call(&mut self, req: tonic::Request<Vec<u8>>) -> Self::Future {
+        let result = (self.0)(req).map(|r| {
+            r.map(|messages| tokio_stream::iter(messages.into_iter().map(Ok).collect::<Vec<_>>()))
+        });
+        let fut = async move { result };
 
         Box::pin(fut)
     }
@@ -25,7 +51,7 @@ impl Codec for GenericCodec {
     type Decoder = GenericProstDecoder;
 
     fn encoder(&mut self) -> Self::Encoder {
-        GenericProstEncoder(Vec::default())
+        GenericProstEncoder
     }
 
     fn decoder(&mut self) -> Self::Decoder {
@@ -35,7 +61,7 @@ impl Codec for GenericCodec {
 
 /// A [`Encoder`] that knows how to encode `T`.
 #[derive(Debug, Clone, Default)]
-pub struct GenericProstEncoder(Vec<u8>);
+pub struct GenericProstEncoder;
 
 impl tonic::codec::Encoder for GenericProstEncoder {
     type Item = Vec<u8>;
@@ -55,7 +81,7 @@ impl tonic::codec::Encoder for GenericProstEncoder {
     }
 }
 
-/// A [`Decoder`] that knows how to decode `U`.
+/// A [`Decoder`] that returns the encoded message as is.
 #[derive(Debug, Clone, Default)]
 pub struct GenericProstDecoder;
 
@@ -67,16 +93,132 @@ impl tonic::codec::Decoder for GenericProstDecoder {
         &mut self,
         buf: &mut tonic::codec::DecodeBuf<'_>,
     ) -> Result<Option<Self::Item>, Self::Error> {
-        let item = Message::decode(buf)
-            .map(Option::Some)
-            .map_err(from_decode_error)?;
+        // keep the raw message so matchers can decode it into the type they expect
+        let item = buf.copy_to_bytes(buf.remaining()).to_vec();
+
+        Ok(Some(item))
+    }
+}
+
+/// Accepts the connections of the server, so they can be reset by a [`ConnectionFault::Reset`](crate::ConnectionFault::Reset).
+pub(crate) struct Incoming {
+    pending: Option<std::net::TcpListener>,
+    listener: Option<tokio::net::TcpListener>,
+    connection_faults: Arc<RwLock<ConnectionFaults>>,
+}
+
+impl Incoming {
+    /// ## Panics
+    /// * When the address is not available.
+    pub(crate) fn bind(
+        address: SocketAddr,
+        connection_faults: Arc<RwLock<ConnectionFaults>>,
+    ) -> Self {
+        let listener = std::net::TcpListener::bind(address)
+            .unwrap_or_else(|e| panic!("Unable to listen to {}: {}", address, e));
+        listener
+            .set_nonblocking(true)
+            .expect("Unable to set the listener non-blocking");
+
+        Self {
+            pending: Some(listener),
+            listener: None,
+            connection_faults,
+        }
+    }
+}
+
+impl Stream for Incoming {
+    type Item = io::Result<Connection>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        // register the listener with the runtime the server runs on (see `ServerRuntime`)
+        if let Some(pending) = this.pending.take() {
+            match tokio::net::TcpListener::from_std(pending) {
+                Ok(listener) => this.listener = Some(listener),
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
 
-        Ok(item)
+        let listener = this.listener.as_ref().unwrap();
+        let (stream, remote_addr) = match listener.poll_accept(cx) {
+            Poll::Ready(Ok(accepted)) => accepted,
+            Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let reset = Arc::default();
+        this.connection_faults
+            .write()
+            .unwrap()
+            .accepted(remote_addr, &reset);
+
+        Poll::Ready(Some(Ok(Connection { stream, reset })))
     }
 }
 
-fn from_decode_error(error: prost::DecodeError) -> tonic::Status {
-    // Map Protobuf parse errors to an INTERNAL status code, as per
-    // https://github.com/grpc/grpc/blob/master/doc/statuscodes.md
-    tonic::Status::new(Code::Internal, error.to_string())
+/// A connection accepted by [`Incoming`], all I/O fails once it is reset.
+pub(crate) struct Connection {
+    stream: tokio::net::TcpStream,
+    reset: Arc<ResetSwitch>,
+}
+
+impl Connection {
+    fn poll_reset(&self, cx: &Context<'_>) -> Option<io::Error> {
+        if !self.reset.poll(cx) {
+            return None;
+        }
+
+        // a zero linger does not block on close, it makes it send a RST instead of a FIN
+        #[allow(deprecated)]
+        let _ = self.stream.set_linger(Some(Duration::ZERO));
+        Some(io::ErrorKind::ConnectionReset.into())
+    }
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if let Some(e) = self.poll_reset(cx) {
+            return Poll::Ready(Err(e));
+        }
+        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if let Some(e) = self.poll_reset(cx) {
+            return Poll::Ready(Err(e));
+        }
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some(e) = self.poll_reset(cx) {
+            return Poll::Ready(Err(e));
+        }
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+impl Connected for Connection {
+    type ConnectInfo = TcpConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.stream.connect_info()
+    }
 }
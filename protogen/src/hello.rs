@@ -24,6 +24,20 @@ pub struct WeatherReply {
     #[prost(string, tag = "1")]
     pub weather: ::prost::alloc::string::String,
 }
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListGreetingsRequest {
+    #[prost(string, tag = "1")]
+    pub page_token: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListGreetingsReply {
+    #[prost(message, repeated, tag = "1")]
+    pub greetings: ::prost::alloc::vec::Vec<HelloReply>,
+    #[prost(string, tag = "2")]
+    pub next_page_token: ::prost::alloc::string::String,
+}
 /// Generated client implementations.
 pub mod greeter_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
@@ -131,6 +145,47 @@ pub mod greeter_client {
             );
             self.inner.unary(request.into_request(), path, codec).await
         }
+        pub async fn say_hello_stream(
+            &mut self,
+            request: impl tonic::IntoRequest<super::HelloRequest>,
+        ) -> Result<
+            tonic::Response<tonic::codec::Streaming<super::HelloReply>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/hello.Greeter/SayHelloStream",
+            );
+            self.inner.server_streaming(request.into_request(), path, codec).await
+        }
+        pub async fn list_greetings(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListGreetingsRequest>,
+        ) -> Result<tonic::Response<super::ListGreetingsReply>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/hello.Greeter/ListGreetings",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -149,6 +204,20 @@ pub mod greeter_server {
             &self,
             request: tonic::Request<super::WeatherRequest>,
         ) -> Result<tonic::Response<super::WeatherReply>, tonic::Status>;
+        /// Server streaming response type for the SayHelloStream method.
+        type SayHelloStreamStream: futures_core::Stream<
+                Item = Result<super::HelloReply, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        async fn say_hello_stream(
+            &self,
+            request: tonic::Request<super::HelloRequest>,
+        ) -> Result<tonic::Response<Self::SayHelloStreamStream>, tonic::Status>;
+        async fn list_greetings(
+            &self,
+            request: tonic::Request<super::ListGreetingsRequest>,
+        ) -> Result<tonic::Response<super::ListGreetingsReply>, tonic::Status>;
     }
     /// The greeting service definition.
     #[derive(Debug)]
@@ -284,6 +353,87 @@ pub mod greeter_server {
                     };
                     Box::pin(fut)
                 }
+                "/hello.Greeter/SayHelloStream" => {
+                    #[allow(non_camel_case_types)]
+                    struct SayHelloStreamSvc<T: Greeter>(pub Arc<T>);
+                    impl<
+                        T: Greeter,
+                    > tonic::server::ServerStreamingService<super::HelloRequest>
+                    for SayHelloStreamSvc<T> {
+                        type Response = super::HelloReply;
+                        type ResponseStream = T::SayHelloStreamStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::HelloRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).say_hello_stream(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = SayHelloStreamSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/hello.Greeter/ListGreetings" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListGreetingsSvc<T: Greeter>(pub Arc<T>);
+                    impl<
+                        T: Greeter,
+                    > tonic::server::UnaryService<super::ListGreetingsRequest>
+                    for ListGreetingsSvc<T> {
+                        type Response = super::ListGreetingsReply;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListGreetingsRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).list_greetings(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ListGreetingsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         Ok(
@@ -10,7 +10,7 @@ use wiremock_grpc::*;
 async fn codegen_works() {
     let server = Server::start_default().await;
 
-    assert!(std::net::TcpStream::connect(&server.address()).is_ok())
+    assert!(std::net::TcpStream::connect(server.address()).is_ok())
 }
 
 //
@@ -73,7 +73,7 @@ impl Server {
     }
 
     async fn start_internal(&mut self) -> Self {
-        let address = self.address().clone();
+        let address = *self.address();
         let thread = tokio::spawn(
             tonic::transport::Server::builder()
                 .add_service(self.clone())
@@ -2,7 +2,9 @@ mod wiremock_gen {
     wiremock_grpc::generate!("hello.Greeter", MyMockServer);
 }
 
-use std::net::TcpStream;
+use std::{net::TcpStream, time::Duration};
+
+use prost::Message;
 
 use wiremock_gen::*;
 use wiremock_grpc::{
@@ -10,14 +12,15 @@ use wiremock_grpc::{
     *,
 };
 use wiremock_grpc_protogen::{
-    greeter_client::GreeterClient, HelloReply, HelloRequest, WeatherReply, WeatherRequest,
+    greeter_client::GreeterClient, HelloReply, HelloRequest, ListGreetingsReply,
+    ListGreetingsRequest, WeatherReply, WeatherRequest,
 };
 
 #[tokio::test]
 async fn it_starts_with_specified_port() {
     let server = MyMockServer::start(5055).await;
 
-    assert!(TcpStream::connect(&server.address()).is_ok())
+    assert!(TcpStream::connect(server.address()).is_ok())
 }
 
 #[tokio::test]
@@ -180,15 +183,653 @@ async fn unmatched_request_panics() {
     assert_eq!(1, server.rules_unmatched());
 } // panics
 
+#[tokio::test]
+async fn matches_on_tonic_request() {
+    let (mut server, mut client) = create().await;
+
+    let request1 = server.setup(
+        MockBuilder::when()
+            .path("/hello.Greeter/SayHello")
+            .matching(|req: &tonic::Request<Vec<u8>>| {
+                HelloRequest::decode(&req.get_ref()[..])
+                    .map(|r| r.name == "Mustakim")
+                    .unwrap_or(false)
+            })
+            .matching(|req: &tonic::Request<Vec<u8>>| req.metadata().get("x-tenant").is_some())
+            .then()
+            .return_body(|| HelloReply {
+                message: "Hello Mustakim".into(),
+            }),
+    );
+
+    // Act
+    let mut request = tonic::Request::new(HelloRequest {
+        name: "Mustakim".into(),
+    });
+    request
+        .metadata_mut()
+        .insert("x-tenant", "acme".parse().unwrap());
+    let response = client.say_hello(request).await.unwrap();
+
+    assert_eq!("Hello Mustakim", response.into_inner().message);
+
+    // different body does not match
+    let response = client
+        .say_hello(HelloRequest {
+            name: "Someone else".into(),
+        })
+        .await;
+
+    assert_eq!(Code::Unimplemented, response.err().unwrap().code());
+    assert_eq!(1, server.find(&request1).unwrap().len());
+}
+
+#[tokio::test]
+async fn panicking_matcher_does_not_break_the_server() {
+    let (mut server, mut client) = create().await;
+
+    let request1 = server.setup(
+        MockBuilder::when()
+            .path("/hello.Greeter/SayHello")
+            .matching(|req: &tonic::Request<Vec<u8>>| {
+                HelloRequest::decode(&req.get_ref()[..]).unwrap().name != "boom"
+            })
+            .then()
+            .return_body(|| HelloReply {
+                message: "Hello Mustakim".into(),
+            }),
+    );
+
+    // Act
+    let response = client
+        .say_hello(HelloRequest {
+            name: "boom".into(),
+        })
+        .await;
+
+    assert!(response.is_err());
+
+    let mut client = connect(&server).await;
+    let response = client
+        .say_hello(HelloRequest {
+            name: "Mustakim".into(),
+        })
+        .await
+        .unwrap();
+
+    assert_eq!("Hello Mustakim", response.into_inner().message);
+    assert_eq!(1, server.find(&request1).unwrap().len());
+}
+
+#[tokio::test(start_paused = true)]
+async fn dedicated_runtime_clock_is_not_paused() {
+    let mut server = MyMockServer::start_with_runtime(ServerRuntime::Dedicated).await;
+    let mut client = connect(&server).await;
+
+    // the time on the server when the request is matched
+    let matched_at = std::sync::Arc::new(std::sync::Mutex::new(None));
+    let m = matched_at.clone();
+    server.setup(
+        MockBuilder::given("/hello.Greeter/SayHello")
+            .matching(move |_: &tonic::Request<Vec<u8>>| {
+                *m.lock().unwrap() = Some(tokio::time::Instant::now());
+                true
+            })
+            .return_body(|| HelloReply {
+                message: "Hello Mustakim".into(),
+            }),
+    );
+
+    // Act
+    let started = tokio::time::Instant::now();
+    tokio::time::advance(Duration::from_secs(3600)).await;
+    client
+        .say_hello(HelloRequest {
+            name: "Mustakim".into(),
+        })
+        .await
+        .unwrap();
+
+    // on the ambient runtime the server would see the hour skipped by the test
+    let matched_at = matched_at.lock().unwrap().unwrap();
+    assert!(matched_at < started + Duration::from_secs(3600));
+}
+
+#[test]
+fn dedicated_runtime_outlives_the_starting_runtime() {
+    let starting = tokio::runtime::Runtime::new().unwrap();
+    let mut server = starting.block_on(MyMockServer::start_with_runtime(ServerRuntime::Dedicated));
+    drop(starting);
+
+    assert_served_from_another_runtime(&mut server);
+}
+
+#[test]
+fn handle_runtime_serves_from_the_given_runtime() {
+    let serving = tokio::runtime::Runtime::new().unwrap();
+    let starting = tokio::runtime::Runtime::new().unwrap();
+    let mut server = starting.block_on(MyMockServer::start_with_runtime(ServerRuntime::Handle(
+        serving.handle().clone(),
+    )));
+    drop(starting);
+
+    assert_served_from_another_runtime(&mut server);
+}
+
+#[allow(dead_code)]
+fn assert_served_from_another_runtime(server: &mut MyMockServer) {
+    server.setup(
+        MockBuilder::given("/hello.Greeter/SayHello").return_body(|| HelloReply {
+            message: "Hello Mustakim".into(),
+        }),
+    );
+
+    let response = tokio::runtime::Runtime::new().unwrap().block_on(async {
+        connect(server)
+            .await
+            .say_hello(HelloRequest {
+                name: "Mustakim".into(),
+            })
+            .await
+            .unwrap()
+    });
+
+    assert_eq!("Hello Mustakim", response.into_inner().message);
+}
+
+#[tokio::test]
+async fn stubs_round_trip_through_json() {
+    let (mut server, _) = create().await;
+
+    server.setup(
+        MockBuilder::given("/hello.Greeter/SayHello").return_body(|| HelloReply {
+            message: "Hello Mustakim".into(),
+        }),
+    );
+    server.setup(MockBuilder::given("/hello.Greeter/WeatherInfo").return_status(Code::NotFound));
+
+    let json = server.export_stubs().unwrap();
+
+    server.setup(
+        MockBuilder::given("/hello.Greeter/SayHello")
+            .matching(|_: &tonic::Request<Vec<u8>>| true)
+            .return_status(Code::Ok),
+    );
+    assert!(matches!(
+        server.export_stubs(),
+        Err(StubMappingError::Unsupported { path, .. }) if path == "/hello.Greeter/SayHello"
+    ));
+    server.reset();
+
+    // Act
+    let (mut server, mut client) = create().await;
+    assert_eq!(2, server.import_stubs(&json).unwrap());
+
+    let response = client
+        .say_hello(HelloRequest {
+            name: "Mustakim".into(),
+        })
+        .await
+        .unwrap();
+    assert_eq!("Hello Mustakim", response.into_inner().message);
+
+    let response = client
+        .weather_info(WeatherRequest {
+            city: "London".into(),
+        })
+        .await;
+    assert_eq!(Code::NotFound, response.err().unwrap().code());
+
+    assert!(matches!(
+        server.import_stubs("{\"mappings\": 1}"),
+        Err(StubMappingError::Json(_))
+    ));
+    assert!(matches!(
+        server.import_stubs(&json.replace("\"5\"", "\"42\"")),
+        Err(StubMappingError::InvalidStatus(s)) if s == "42"
+    ));
+    assert_eq!(2, server.rules_len());
+}
+
+#[tokio::test]
+async fn calls_filtered_by_tag() {
+    let (mut server, mut client) = create().await;
+
+    server.setup(
+        MockBuilder::when()
+            .path("/hello.Greeter/SayHello")
+            .then()
+            .return_body(|| HelloReply {
+                message: "Hello Mustakim".into(),
+            })
+            .with_tag("critical-path"),
+    );
+    server.setup(
+        MockBuilder::given("/hello.Greeter/WeatherInfo")
+            .return_body(|| WeatherReply {
+                weather: "rainy, as always".into(),
+            })
+            .with_tag("weather"),
+    );
+
+    // Act
+    client
+        .say_hello(HelloRequest {
+            name: "Mustakim".into(),
+        })
+        .await
+        .unwrap();
+    client
+        .weather_info(WeatherRequest {
+            city: "London".into(),
+        })
+        .await
+        .unwrap();
+
+    let calls = server.calls_with_tag("critical-path");
+    assert_eq!(1, calls.len());
+    assert!(calls[0].uri.ends_with("/hello.Greeter/SayHello"));
+    assert!(server.calls_with_tag("unknown").is_empty());
+}
+
+#[tokio::test]
+async fn retry_gap() {
+    let (mut server, mut client) = create().await;
+
+    server.setup(MockBuilder::given("/hello.Greeter/SayHello").return_status(Code::Unavailable));
+
+    // Act
+    for _ in 0..3 {
+        let response = client
+            .say_hello(HelloRequest {
+                name: "Mustakim".into(),
+            })
+            .await;
+        assert_eq!(Code::Unavailable, response.err().unwrap().code());
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    server.assert_client_retry_gap_at_least("/hello.Greeter/SayHello", Duration::from_millis(50));
+}
+
+#[tokio::test(start_paused = true)]
+async fn retry_gap_with_paused_time() {
+    let (mut server, mut client) = create().await;
+
+    server.setup(MockBuilder::given("/hello.Greeter/SayHello").return_status(Code::Unavailable));
+
+    // Act
+    for _ in 0..2 {
+        let _ = client
+            .say_hello(HelloRequest {
+                name: "Mustakim".into(),
+            })
+            .await;
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    server.assert_client_retry_gap_at_least("/hello.Greeter/SayHello", Duration::from_secs(1));
+}
+
+#[tokio::test]
+#[should_panic(expected = "Request 2 to /hello.Greeter/SayHello was received")]
+async fn retry_gap_too_short() {
+    let (mut server, mut client) = create().await;
+
+    server.setup(MockBuilder::given("/hello.Greeter/SayHello").return_status(Code::Unavailable));
+
+    // Act
+    for _ in 0..2 {
+        let _ = client
+            .say_hello(HelloRequest {
+                name: "Mustakim".into(),
+            })
+            .await;
+    }
+
+    server.assert_client_retry_gap_at_least("/hello.Greeter/SayHello", Duration::from_secs(10));
+}
+
+#[tokio::test(start_paused = true)]
+async fn maintenance_window_with_paused_time() {
+    let (mut server, mut client) = create().await;
+
+    server.setup(
+        MockBuilder::given("/hello.Greeter/SayHello").return_body(|| HelloReply {
+            message: "Hello Mustakim".into(),
+        }),
+    );
+
+    server.start_maintenance(Duration::from_secs(5));
+    assert!(server.in_maintenance());
+
+    // Act
+    tokio::time::sleep(Duration::from_secs(6)).await;
+
+    assert!(!server.in_maintenance());
+    let response = client
+        .say_hello(HelloRequest {
+            name: "Mustakim".into(),
+        })
+        .await
+        .unwrap();
+    assert_eq!("Hello Mustakim", response.into_inner().message);
+}
+
+#[tokio::test]
+async fn maintenance_window() {
+    // google.rpc.Status / google.rpc.RetryInfo
+    #[derive(Clone, PartialEq, Message)]
+    struct StatusDetails {
+        #[prost(message, repeated, tag = "3")]
+        details: Vec<prost_types::Any>,
+    }
+    #[derive(Clone, PartialEq, Message)]
+    struct RetryInfo {
+        #[prost(message, optional, tag = "1")]
+        retry_delay: Option<prost_types::Duration>,
+    }
+
+    let (mut server, mut client) = create().await;
+
+    server.setup(
+        MockBuilder::given("/hello.Greeter/SayHello").return_body(|| HelloReply {
+            message: "Hello Mustakim".into(),
+        }),
+    );
+
+    server.start_maintenance(Duration::from_millis(200));
+    assert!(server.in_maintenance());
+
+    // Act
+    let status = client
+        .say_hello(HelloRequest {
+            name: "Mustakim".into(),
+        })
+        .await
+        .err()
+        .unwrap();
+
+    assert_eq!(Code::Unavailable, status.code());
+    let details = StatusDetails::decode(status.details()).unwrap();
+    assert_eq!(
+        "type.googleapis.com/google.rpc.RetryInfo",
+        details.details[0].type_url
+    );
+    let retry_delay = RetryInfo::decode(&details.details[0].value[..])
+        .unwrap()
+        .retry_delay
+        .unwrap();
+    assert!(retry_delay.seconds == 0 && retry_delay.nanos > 0);
+
+    // flips back once the window ends
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let response = client
+        .say_hello(HelloRequest {
+            name: "Mustakim".into(),
+        })
+        .await
+        .unwrap();
+
+    assert_eq!("Hello Mustakim", response.into_inner().message);
+    assert!(!server.in_maintenance());
+    assert_eq!(2, server.find_request_count());
+}
+
+#[tokio::test]
+async fn custom_matcher_from_stub_mappings() {
+    let (mut server, mut client) = create().await;
+
+    server.register_matcher("name-is", |parameters: &serde_json::Value| {
+        let name = parameters["name"].as_str().unwrap_or_default().to_string();
+        move |req: &tonic::Request<Vec<u8>>| {
+            HelloRequest::decode(&req.get_ref()[..])
+                .map(|r| r.name == name)
+                .unwrap_or(false)
+        }
+    });
+
+    let json = r#"{
+        "mappings": [
+            {
+                "request": {
+                    "urlPath": "/hello.Greeter/SayHello",
+                    "customMatcher": { "name": "name-is", "parameters": { "name": "Mustakim" } }
+                },
+                "response": { "headers": { "grpc-status": "7" } }
+            }
+        ]
+    }"#;
+    assert_eq!(1, server.import_stubs(json).unwrap());
+    assert!(matches!(
+        server.import_stubs(&json.replace("name-is", "unknown")),
+        Err(StubMappingError::UnknownMatcher(name)) if name == "unknown"
+    ));
+
+    // Act
+    let response = client
+        .say_hello(HelloRequest {
+            name: "Mustakim".into(),
+        })
+        .await;
+    assert_eq!(Code::PermissionDenied, response.err().unwrap().code());
+
+    let response = client
+        .say_hello(HelloRequest {
+            name: "Someone else".into(),
+        })
+        .await;
+    assert_eq!(Code::Unimplemented, response.err().unwrap().code());
+
+    assert!(server.export_stubs().unwrap().contains("\"name-is\""));
+}
+
+#[tokio::test]
+async fn connection_faults() {
+    let (mut server, mut client1) = create().await;
+    let mut client2 = connect(&server).await;
+
+    server.setup(
+        MockBuilder::given("/hello.Greeter/SayHello").return_body(|| HelloReply {
+            message: "Hello Mustakim".into(),
+        }),
+    );
+    server.add_connection_fault(ConnectionScope::Nth(0), ConnectionFault::Reset);
+    server.add_connection_fault(
+        ConnectionScope::matching(|addr| addr.ip().is_loopback()),
+        ConnectionFault::Delay(Duration::from_millis(100)),
+    );
+
+    // Act
+    let response = client1
+        .say_hello(HelloRequest {
+            name: "Mustakim".into(),
+        })
+        .await;
+    assert!(response.is_err(), "The first connection must be reset");
+
+    let started = std::time::Instant::now();
+    let response = client2
+        .say_hello(HelloRequest {
+            name: "Mustakim".into(),
+        })
+        .await
+        .unwrap();
+    assert_eq!("Hello Mustakim", response.into_inner().message);
+    assert!(started.elapsed() >= Duration::from_millis(100));
+
+    // the channel reconnects, the new connection is not the first one
+    let response = client1
+        .say_hello(HelloRequest {
+            name: "Mustakim".into(),
+        })
+        .await
+        .unwrap();
+    assert_eq!("Hello Mustakim", response.into_inner().message);
+    assert_eq!(2, server.find_request_count());
+}
+
+#[tokio::test]
+async fn pagination() {
+    let (mut server, mut client) = create().await;
+
+    let greetings = (1..=5)
+        .map(|i| HelloReply {
+            message: format!("{}", i),
+        })
+        .collect::<Vec<_>>();
+    server.setup(
+        MockBuilder::given("/hello.Greeter/ListGreetings").return_pages(
+            Pagination::new(greetings, 2)
+                .page_token(|req: ListGreetingsRequest| req.page_token)
+                .response(|greetings, next_page_token| ListGreetingsReply {
+                    greetings,
+                    next_page_token,
+                }),
+        ),
+    );
+
+    // Act
+    let mut pages = Vec::default();
+    let mut page_token = String::default();
+    loop {
+        let reply = client
+            .list_greetings(ListGreetingsRequest { page_token })
+            .await
+            .unwrap()
+            .into_inner();
+        pages.push(
+            reply
+                .greetings
+                .into_iter()
+                .map(|g| g.message)
+                .collect::<Vec<_>>(),
+        );
+        if reply.next_page_token.is_empty() {
+            break;
+        }
+        page_token = reply.next_page_token;
+    }
+
+    assert_eq!(vec![vec!["1", "2"], vec!["3", "4"], vec!["5"]], pages);
+
+    let response = client
+        .list_greetings(ListGreetingsRequest {
+            page_token: "not-a-token".into(),
+        })
+        .await;
+    assert_eq!(Code::InvalidArgument, response.err().unwrap().code());
+}
+
+#[tokio::test]
+async fn clock_skewed_generated_response() {
+    let (mut server, mut client) = create().await;
+
+    server.set_clock_skew(ClockSkew::Ahead(Duration::from_secs(3600)));
+    server.setup(
+        MockBuilder::given("/hello.Greeter/SayHello").return_body_with(|now| HelloReply {
+            message: now
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+                .to_string(),
+        }),
+    );
+
+    // Act
+    let response = client
+        .say_hello(HelloRequest {
+            name: "Mustakim".into(),
+        })
+        .await
+        .unwrap();
+
+    let issued_at: u64 = response.into_inner().message.parse().unwrap();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    assert!((now + 3599..=now + 3600).contains(&issued_at));
+}
+
+#[tokio::test]
+#[should_panic(expected = "The clock skew Behind")]
+async fn clock_skew_out_of_range_panics() {
+    let (server, _) = create().await;
+
+    server.set_clock_skew(ClockSkew::Behind(Duration::MAX));
+}
+
+#[tokio::test]
+async fn inspect_stubs() {
+    let (mut server, mut client) = create().await;
+
+    server.setup(
+        MockBuilder::given("/hello.Greeter/SayHello")
+            .matching(|_: &tonic::Request<Vec<u8>>| true)
+            .return_body(|| HelloReply {
+                message: "Hello Mustakim".into(),
+            })
+            .with_tag("critical-path"),
+    );
+    server.register_matcher("any", |_: &serde_json::Value| {
+        |_: &tonic::Request<Vec<u8>>| true
+    });
+    server
+        .import_stubs(
+            r#"{ "mappings": [ {
+                "request": { "urlPath": "/hello.Greeter/WeatherInfo", "customMatcher": { "name": "any" } },
+                "response": { "headers": { "grpc-status": "5" } }
+            } ] }"#,
+        )
+        .unwrap();
+
+    // Act
+    client
+        .say_hello(HelloRequest {
+            name: "Mustakim".into(),
+        })
+        .await
+        .unwrap();
+    let _ = client
+        .weather_info(WeatherRequest {
+            city: "London".into(),
+        })
+        .await;
+
+    let stubs = server.stubs();
+    assert_eq!(2, stubs.len());
+
+    assert_eq!("/hello.Greeter/SayHello", stubs[0].path);
+    assert_eq!(vec!["custom matcher"], stubs[0].matchers);
+    assert_eq!(ResponseKind::Body { len: 16 }, stubs[0].response);
+    assert_eq!(0, stubs[0].priority);
+    assert_eq!(1, stubs[0].hit_count);
+    assert_eq!(vec!["critical-path"], stubs[0].tags);
+
+    assert_eq!(vec!["any"], stubs[1].matchers);
+    assert_eq!(ResponseKind::Status(Code::NotFound), stubs[1].response);
+    assert_eq!(1, stubs[1].priority);
+    assert_eq!(1, stubs[1].hit_count);
+}
+
 #[allow(dead_code)]
 async fn create() -> (MyMockServer, GreeterClient<Channel>) {
     let server = MyMockServer::start_default().await;
+    let client = connect(&server).await;
 
+    (server, client)
+}
+
+#[allow(dead_code)]
+async fn connect(server: &GrpcServer) -> GreeterClient<Channel> {
     let channel =
         tonic::transport::Channel::from_shared(format!("http://[::1]:{}", server.address().port()))
             .unwrap()
             .connect()
             .await
             .unwrap();
-    return (server, GreeterClient::new(channel));
+    GreeterClient::new(channel)
 }
@@ -4,5 +4,7 @@ mod codegen_test;
 mod features_test;
 #[allow(unused_imports)]
 mod mocks_test;
+#[allow(unused_imports, dead_code)]
+mod streaming_test;
 #[allow(unused_imports)]
 mod validations_test;
@@ -0,0 +1,143 @@
+mod wiremock_gen {
+    wiremock_grpc::generate!("hello.Greeter", MyMockServer);
+}
+
+use prost::Message;
+use wiremock_gen::*;
+use wiremock_grpc::{tonic::transport::Channel, *};
+use wiremock_grpc_protogen::{greeter_client::GreeterClient, HelloReply, HelloRequest};
+
+fn replies() -> Vec<HelloReply> {
+    (1..=8)
+        .map(|i| HelloReply {
+            message: format!("{}", i),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn server_stream() {
+    let (mut server, mut client) = create().await;
+
+    server.setup(MockBuilder::given("/hello.Greeter/SayHelloStream").return_stream(replies));
+
+    // Act
+    let messages = receive_all(&mut client).await;
+
+    assert_eq!(vec!["1", "2", "3", "4", "5", "6", "7", "8"], messages);
+}
+
+#[tokio::test]
+async fn server_stream_reordered() {
+    let (mut server, mut client) = create().await;
+
+    server.setup(
+        MockBuilder::given("/hello.Greeter/SayHelloStream")
+            .return_stream(replies)
+            .with_stream_fault(StreamFault::Reorder { seed: 42 }),
+    );
+
+    // Act
+    let first = receive_all(&mut client).await;
+    let second = receive_all(&mut client).await;
+
+    assert_eq!(first, second, "The same seed must give the same order");
+    assert_ne!(vec!["1", "2", "3", "4", "5", "6", "7", "8"], first);
+
+    let mut sorted = first.clone();
+    sorted.sort();
+    assert_eq!(vec!["1", "2", "3", "4", "5", "6", "7", "8"], sorted);
+}
+
+#[tokio::test]
+async fn server_stream_duplicated() {
+    let (mut server, mut client) = create().await;
+
+    server.setup(
+        MockBuilder::given("/hello.Greeter/SayHelloStream")
+            .return_stream(replies)
+            .with_stream_fault(StreamFault::Duplicate { seed: 42 }),
+    );
+
+    // Act
+    let mut messages = receive_all(&mut client).await;
+
+    assert!(messages.len() > 8);
+    messages.dedup();
+    assert_eq!(vec!["1", "2", "3", "4", "5", "6", "7", "8"], messages);
+}
+
+#[tokio::test]
+async fn server_stream_from_ndjson() {
+    let (mut server, mut client) = create().await;
+
+    server.setup(
+        MockBuilder::given("/hello.Greeter/SayHelloStream").return_stream_from_ndjson(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/replies.ndjson"),
+            |line| HelloReply {
+                message: line["message"].as_str().unwrap().into(),
+            },
+        ),
+    );
+
+    // Act
+    let messages = receive_all(&mut client).await;
+
+    assert_eq!(vec!["1", "2", "3"], messages);
+}
+
+#[tokio::test]
+async fn server_stream_from_length_delimited() {
+    let path = std::env::temp_dir().join(format!("replies-{}.bin", std::process::id()));
+    let mut content = Vec::default();
+    for reply in replies() {
+        reply.encode_length_delimited(&mut content).unwrap();
+    }
+    std::fs::write(&path, content).unwrap();
+
+    let (mut server, mut client) = create().await;
+
+    server.setup(
+        MockBuilder::given("/hello.Greeter/SayHelloStream")
+            .return_stream_from_length_delimited(&path),
+    );
+    std::fs::remove_file(&path).unwrap();
+
+    // Act
+    let messages = receive_all(&mut client).await;
+
+    assert_eq!(vec!["1", "2", "3", "4", "5", "6", "7", "8"], messages);
+}
+
+async fn receive_all(client: &mut GreeterClient<Channel>) -> Vec<String> {
+    let mut stream = client
+        .say_hello_stream(HelloRequest {
+            name: "Mustakim".into(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let mut messages = Vec::default();
+    while let Some(reply) = stream.message().await.unwrap() {
+        messages.push(reply.message);
+    }
+    messages
+}
+
+async fn create() -> (MyMockServer, GreeterClient<Channel>) {
+    let server = MyMockServer::start_default().await;
+    let client = connect(&server).await;
+
+    (server, client)
+}
+
+async fn connect(server: &GrpcServer) -> GreeterClient<Channel> {
+    let channel =
+        tonic::transport::Channel::from_shared(format!("http://[::1]:{}", server.address().port()))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+    GreeterClient::new(channel)
+}